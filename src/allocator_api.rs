@@ -0,0 +1,150 @@
+//! Implementation of the unstable [`core::alloc::Allocator`] trait for
+//! [`O1Heap`].
+//!
+//! Gated behind the `allocator_api` cargo feature, which requires a nightly
+//! compiler since `Allocator` has not been stabilized. This lets a single
+//! [`O1Heap`] instance back an allocator-aware collection (`Box::new_in`,
+//! `Vec::with_capacity_in`, ...) instead of being limited to
+//! [`core::alloc::GlobalAlloc`]'s single program-wide heap, which is useful
+//! when a program wants a distinct bounded-latency arena per subsystem.
+//!
+//! [`LockedO1Heap`] gets the same treatment so it can back allocator-aware
+//! collections across multiple cores.
+
+use core::alloc::{AllocError, Allocator, Layout};
+use core::ptr::NonNull;
+
+use lock_api::RawMutex;
+
+use crate::{LockedO1Heap, O1Heap, ALIGNMENT};
+
+/// Crate-private primitive allocate/free pair backing the [`Allocator`] impl
+/// below, so the impl only needs to be written once for every type in this
+/// crate that exposes `allocate`/`free`.
+trait RawAlloc {
+    fn raw_allocate(&self, size: usize) -> Option<NonNull<u8>>;
+
+    /// # Safety
+    /// Same contract as [`O1Heap::free`](crate::O1Heap::free).
+    unsafe fn raw_free(&self, ptr: NonNull<u8>);
+}
+
+impl RawAlloc for O1Heap {
+    fn raw_allocate(&self, size: usize) -> Option<NonNull<u8>> {
+        self.allocate(size)
+    }
+
+    unsafe fn raw_free(&self, ptr: NonNull<u8>) {
+        unsafe { self.free(ptr) }
+    }
+}
+
+impl<R: RawMutex> RawAlloc for LockedO1Heap<R> {
+    fn raw_allocate(&self, size: usize) -> Option<NonNull<u8>> {
+        self.allocate(size)
+    }
+
+    unsafe fn raw_free(&self, ptr: NonNull<u8>) {
+        unsafe { self.free(ptr) }
+    }
+}
+
+// `Allocator` is a foreign trait, so the orphan rules reject a blanket
+// `impl<T: RawAlloc> Allocator for T`; this macro stamps the one shared impl
+// out for each concrete `RawAlloc` type instead.
+macro_rules! impl_allocator {
+    ([$($generics:tt)*] $ty:ty) => {
+unsafe impl<$($generics)*> Allocator for $ty {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            // o1heapAllocate rejects zero-byte requests, but Allocator's
+            // contract requires zero-sized layouts to always succeed with a
+            // dangling, layout-aligned pointer -- the same convention
+            // `System`/`Global` use.
+            let ptr = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(ptr, 0));
+        }
+        if layout.align() > ALIGNMENT {
+            // o1heap only ever hands back ALIGNMENT-aligned fragments, and
+            // this impl does not over-allocate to reach a larger alignment,
+            // so over-aligned requests are simply unsupported.
+            return Err(AllocError);
+        }
+        let ptr = self.raw_allocate(layout.size()).ok_or(AllocError)?;
+        // o1heap never reports the real size of the fragment it carved out,
+        // so report exactly what was requested rather than guessing at a
+        // larger usable size.
+        let slice = core::ptr::slice_from_raw_parts_mut(ptr.as_ptr(), layout.size());
+        Ok(unsafe { NonNull::new_unchecked(slice) })
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = Allocator::allocate(self, layout)?;
+        unsafe { core::ptr::write_bytes(ptr.as_ptr() as *mut u8, 0, ptr.len()) };
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+        unsafe { self.raw_free(ptr) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() >= old_layout.size());
+        let new_ptr = Allocator::allocate(self, new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr() as *mut u8,
+                old_layout.size(),
+            );
+            self.deallocate(ptr, old_layout);
+        }
+        Ok(new_ptr)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let new_ptr = unsafe { self.grow(ptr, old_layout, new_layout)? };
+        unsafe {
+            let tail = (new_ptr.as_ptr() as *mut u8).add(old_layout.size());
+            core::ptr::write_bytes(tail, 0, new_ptr.len() - old_layout.size());
+        }
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() <= old_layout.size());
+        let new_ptr = Allocator::allocate(self, new_layout)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                ptr.as_ptr(),
+                new_ptr.as_ptr() as *mut u8,
+                new_layout.size(),
+            );
+            self.deallocate(ptr, old_layout);
+        }
+        Ok(new_ptr)
+    }
+}
+    };
+}
+
+impl_allocator!([] O1Heap);
+impl_allocator!([R: RawMutex] LockedO1Heap<R>);
@@ -0,0 +1,56 @@
+//! Idiomatic wrapper around the raw FFI diagnostics struct.
+
+use core::fmt;
+
+use crate::O1HeapDiagnostics;
+
+/// Snapshot of heap usage and health.
+///
+/// Wraps the raw bindgen [`O1HeapDiagnostics`] struct behind named
+/// accessors instead of exposing its FFI layout directly.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostics(O1HeapDiagnostics);
+
+impl Diagnostics {
+    pub(crate) fn new(raw: O1HeapDiagnostics) -> Self {
+        Self(raw)
+    }
+
+    /// Total heap capacity, in bytes, usable for allocations.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity
+    }
+
+    /// Bytes currently allocated.
+    pub fn allocated(&self) -> usize {
+        self.0.allocated
+    }
+
+    /// Highest value `allocated` has ever reached.
+    pub fn peak_allocated(&self) -> usize {
+        self.0.peak_allocated
+    }
+
+    /// Largest single allocation request ever made, successful or not.
+    pub fn peak_request_size(&self) -> usize {
+        self.0.peak_request_size
+    }
+
+    /// Number of allocation requests that failed due to insufficient memory.
+    pub fn oom_count(&self) -> u64 {
+        self.0.oom_count
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} bytes allocated (peak {}), {} OOM event(s)",
+            self.allocated(),
+            self.capacity(),
+            self.peak_allocated(),
+            self.oom_count()
+        )
+    }
+}
@@ -3,8 +3,12 @@
 use core::alloc::{GlobalAlloc, Layout};
 use core::cell::UnsafeCell;
 use core::ffi::c_void;
+use core::ptr::NonNull;
 
-use crate::{o1heapAllocate, o1heapFree, o1heapInit, InitError, O1HeapInstance, ALIGNMENT};
+use crate::{
+    o1heapAllocate, o1heapFree, o1heapInit, raw_global_alloc, raw_global_dealloc,
+    raw_global_realloc, InitError, O1HeapInstance, OnOomCell, ALIGNMENT,
+};
 
 /// A global allocator backed by o1heap.
 ///
@@ -26,6 +30,7 @@ use crate::{o1heapAllocate, o1heapFree, o1heapInit, InitError, O1HeapInstance, A
 /// ```
 pub struct O1HeapGlobalAlloc {
     instance: UnsafeCell<*mut O1HeapInstance>,
+    on_oom: OnOomCell,
 }
 
 // SAFETY: O1HeapGlobalAlloc is designed for single-core embedded systems.
@@ -47,9 +52,23 @@ impl O1HeapGlobalAlloc {
     pub const fn new() -> Self {
         Self {
             instance: UnsafeCell::new(core::ptr::null_mut()),
+            on_oom: OnOomCell::new(),
         }
     }
 
+    /// Registers a callback invoked when an allocation request cannot be
+    /// satisfied, before [`alloc`](GlobalAlloc::alloc) returns null to the
+    /// caller.
+    ///
+    /// The callback receives the failing [`Layout`] and may return a
+    /// fallback pointer, or null to report the failure as usual. A returned
+    /// pointer is later released through [`dealloc`](GlobalAlloc::dealloc)
+    /// like any other allocation, i.e. handed to o1heap's free routine;
+    /// pointers from outside this heap are not told apart.
+    pub fn set_on_oom(&self, callback: fn(Layout) -> *mut u8) {
+        self.on_oom.set(callback);
+    }
+
     /// Initialize the allocator with the given memory arena.
     ///
     /// # Safety
@@ -78,17 +97,32 @@ impl O1HeapGlobalAlloc {
 
 unsafe impl GlobalAlloc for O1HeapGlobalAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // o1heap always returns ALIGNMENT-aligned memory.
-        // For larger alignment requirements, users need a different approach.
-        debug_assert!(
-            layout.align() <= ALIGNMENT,
-            "o1heap cannot satisfy alignment greater than {}",
-            ALIGNMENT
-        );
-        unsafe { o1heapAllocate(self.get(), layout.size()) as *mut u8 }
+        let instance = self.get();
+        raw_global_alloc(
+            layout,
+            |size| NonNull::new(unsafe { o1heapAllocate(instance, size) }.cast()),
+            self.on_oom.get(),
+        )
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        unsafe { o1heapFree(self.get(), ptr as *mut c_void) }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let instance = self.get();
+        unsafe {
+            raw_global_dealloc(ptr, layout, |p| {
+                o1heapFree(instance, p.as_ptr() as *mut c_void)
+            })
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            raw_global_realloc(
+                ptr,
+                layout,
+                new_size,
+                |l| self.alloc(l),
+                |p, l| self.dealloc(p, l),
+            )
+        }
     }
 }
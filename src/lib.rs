@@ -1,13 +1,29 @@
 #![no_std]
 #![allow(non_camel_case_types, non_upper_case_globals, non_snake_case)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
 use core::alloc::{GlobalAlloc, Layout};
 use core::cell::UnsafeCell;
 use core::fmt;
 use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "allocator_api")]
+mod allocator_api;
+mod diagnostics;
+pub mod global_alloc;
+mod locked;
+mod static_heap;
+
+pub use diagnostics::Diagnostics;
+pub use global_alloc::O1HeapGlobalAlloc;
+pub use locked::LockedO1Heap;
+#[cfg(feature = "spin")]
+pub use locked::SpinLockedO1Heap;
+pub use static_heap::O1HeapStatic;
+
 /// Error returned when heap initialization fails.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct InitError;
@@ -25,8 +41,40 @@ impl fmt::Display for InitError {
 /// On 32-bit systems: 16 bytes. On 64-bit systems: 32 bytes.
 pub const ALIGNMENT: usize = core::mem::size_of::<*const ()>() * 4;
 
+/// A `Sync` cell holding an optional `on_oom` callback.
+///
+/// A bare `UnsafeCell` would let one core call `set` while another
+/// concurrently `get`s, racing on the read/write of the callback pointer --
+/// unsound from 100% safe calling code on a type that's `unsafe impl Sync`.
+/// The callback is a single pointer-sized value, so storing it in an
+/// `AtomicPtr` instead gives every access a well-defined total order without
+/// needing a full lock.
+pub(crate) struct OnOomCell(AtomicPtr<()>);
+
+impl OnOomCell {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicPtr::new(core::ptr::null_mut()))
+    }
+
+    pub(crate) fn set(&self, callback: fn(Layout) -> *mut u8) {
+        self.0.store(callback as *mut (), Ordering::Release);
+    }
+
+    pub(crate) fn get(&self) -> Option<fn(Layout) -> *mut u8> {
+        let ptr = self.0.load(Ordering::Acquire);
+        if ptr.is_null() {
+            None
+        } else {
+            // SAFETY: the only non-null value ever stored is a
+            // `fn(Layout) -> *mut u8` cast to `*mut ()` by `set`.
+            Some(unsafe { core::mem::transmute::<*mut (), fn(Layout) -> *mut u8>(ptr) })
+        }
+    }
+}
+
 pub struct O1Heap {
     instance: UnsafeCell<*mut O1HeapInstance>,
+    on_oom: OnOomCell,
 }
 
 unsafe impl Sync for O1Heap {}
@@ -38,9 +86,26 @@ impl O1Heap {
     pub const fn empty() -> Self {
         Self {
             instance: UnsafeCell::new(core::ptr::null_mut()),
+            on_oom: OnOomCell::new(),
         }
     }
 
+    /// Registers a callback invoked when an allocation request cannot be
+    /// satisfied, before [`alloc`](GlobalAlloc::alloc) returns null to the
+    /// caller.
+    ///
+    /// The callback receives the [`Layout`] that failed to allocate and may
+    /// return a fallback pointer or null to report the failure as usual.
+    /// A returned pointer is later released through
+    /// [`dealloc`](GlobalAlloc::dealloc) like any other allocation, i.e.
+    /// handed to o1heap's free routine; pointers from outside this heap are
+    /// not told apart. This gives embedded users a single
+    /// place to log peak-usage watermarks or react to exhaustion without
+    /// having to poll [`diagnostics`](Self::diagnostics) manually.
+    pub fn set_on_oom(&self, callback: fn(Layout) -> *mut u8) {
+        self.on_oom.set(callback);
+    }
+
     /// Initialize the heap with the given memory arena.
     ///
     /// # Safety
@@ -99,10 +164,10 @@ impl O1Heap {
     }
 
     /// Get diagnostic information about the heap.
-    pub fn diagnostics(&self) -> O1HeapDiagnostics {
+    pub fn diagnostics(&self) -> Diagnostics {
         let instance = self.get();
         debug_assert!(!instance.is_null(), "O1Heap not initialized");
-        unsafe { o1heapGetDiagnostics(instance) }
+        Diagnostics::new(unsafe { o1heapGetDiagnostics(instance) })
     }
 }
 
@@ -111,21 +176,199 @@ pub fn min_arena_size() -> usize {
     unsafe { o1heapMinArenaSize }
 }
 
-unsafe impl GlobalAlloc for O1Heap {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        debug_assert!(
-            layout.align() <= ALIGNMENT,
-            "o1heap cannot satisfy alignment greater than {}",
-            ALIGNMENT
-        );
-        self.allocate(layout.size())
+/// Size, in bytes, of o1heap's internal per-fragment header.
+pub(crate) const FRAGMENT_HEADER_SIZE: usize = ALIGNMENT;
+
+/// Smallest fragment o1heap will ever hand out.
+pub(crate) const MIN_FRAGMENT_SIZE: usize = ALIGNMENT * 2;
+
+/// Rounds `size` up to the power-of-two bin o1heap uses to index its free
+/// list for a request of that size (header included).
+///
+/// o1heap is a segregated/TLSF-style allocator: these power-of-two classes
+/// only bin *free* fragments for O(1) lookup. The fragment actually carved
+/// out for a live allocation is trimmed to `ALIGNMENT` granularity, with the
+/// remainder split back into the free list -- so two sizes sharing a class
+/// are *not* guaranteed to occupy a fragment of the same size, and this
+/// crate must not use `size_class` equality as a basis for reusing an
+/// allocation in place. It's exposed for callers who want a cheap estimate
+/// of o1heap's internal bucketing, e.g. for their own capacity planning.
+pub fn size_class(size: usize) -> usize {
+    size.saturating_add(FRAGMENT_HEADER_SIZE)
+        .next_power_of_two()
+        .max(MIN_FRAGMENT_SIZE)
+}
+
+/// Requests enough extra room from an `ALIGNMENT`-aligned allocator to carve
+/// out an `align`-aligned allocation of `size` bytes via [`over_align`].
+pub(crate) fn over_aligned_request_size(size: usize, align: usize) -> usize {
+    size + align
+}
+
+/// Rounds `base` (a pointer returned by the underlying `ALIGNMENT`-aligned
+/// allocator) up to `align`, stashing `base` in the machine word immediately
+/// preceding the result so [`over_align_base`] can recover it on `dealloc`.
+///
+/// This is the same over-allocate-and-offset technique `liballoc_system`
+/// uses for its large-alignment slow path.
+///
+/// # Safety
+/// `base` must point to at least `over_aligned_request_size(size, align)`
+/// valid, writable bytes.
+pub(crate) unsafe fn over_align(base: NonNull<u8>, align: usize) -> NonNull<u8> {
+    let addr = base.as_ptr() as usize;
+    let aligned_addr = (addr + core::mem::size_of::<usize>() + align - 1) & !(align - 1);
+    let aligned = aligned_addr as *mut u8;
+    unsafe { (aligned as *mut usize).sub(1).write(addr) };
+    unsafe { NonNull::new_unchecked(aligned) }
+}
+
+/// Recovers the original allocation base pointer stored by [`over_align`].
+///
+/// # Safety
+/// `ptr` must have been returned by [`over_align`].
+pub(crate) unsafe fn over_align_base(ptr: NonNull<u8>) -> NonNull<u8> {
+    let base = unsafe { (ptr.as_ptr() as *mut usize).sub(1).read() };
+    unsafe { NonNull::new_unchecked(base as *mut u8) }
+}
+
+/// Shared body of `GlobalAlloc::alloc` for every allocator type in this
+/// crate: handles over-alignment via [`over_align`] and, if `allocate`
+/// fails, gives `on_oom` a chance to supply a fallback pointer before
+/// reporting failure.
+///
+/// `allocate` must behave like [`O1Heap::allocate`]: return a pointer to at
+/// least `size` `ALIGNMENT`-aligned bytes, or `None` on failure.
+pub(crate) fn raw_global_alloc(
+    layout: Layout,
+    allocate: impl FnOnce(usize) -> Option<NonNull<u8>>,
+    on_oom: Option<fn(Layout) -> *mut u8>,
+) -> *mut u8 {
+    let ptr = if layout.align() > ALIGNMENT {
+        let size = over_aligned_request_size(layout.size(), layout.align());
+        match allocate(size) {
+            Some(base) => unsafe { over_align(base, layout.align()) }.as_ptr(),
+            None => core::ptr::null_mut(),
+        }
+    } else {
+        allocate(layout.size())
             .map(|p| p.as_ptr())
             .unwrap_or(core::ptr::null_mut())
+    };
+    if ptr.is_null() {
+        if let Some(callback) = on_oom {
+            return callback(layout);
+        }
+    }
+    ptr
+}
+
+/// Shared body of `GlobalAlloc::dealloc`: recovers the original allocation
+/// base pointer via [`over_align_base`] before handing it to `free` when
+/// `layout` is over-aligned.
+///
+/// # Safety
+/// `ptr`/`layout` must be a pointer/layout pair previously produced by
+/// [`raw_global_alloc`] against the same underlying allocator that `free`
+/// frees into.
+pub(crate) unsafe fn raw_global_dealloc(
+    ptr: *mut u8,
+    layout: Layout,
+    free: impl FnOnce(NonNull<u8>),
+) {
+    let Some(ptr) = NonNull::new(ptr) else {
+        return;
+    };
+    if layout.align() > ALIGNMENT {
+        free(unsafe { over_align_base(ptr) });
+    } else {
+        free(ptr);
+    }
+}
+
+/// Shared body of `GlobalAlloc::realloc`: always falls back to
+/// allocate-copy-free via `alloc`/`dealloc`.
+///
+/// o1heap never reports how large the fragment backing an existing
+/// allocation actually is, so there's no way to tell whether `new_size`
+/// still fits in place; a `size_class(layout.size()) == size_class(new_size)`
+/// shortcut would assume it does and risks writing past the real fragment.
+///
+/// # Safety
+/// `ptr`/`layout` must be a live allocation that `alloc`/`dealloc` can
+/// grow/shrink (i.e. the same contract as `GlobalAlloc::realloc`).
+pub(crate) unsafe fn raw_global_realloc(
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+    alloc: impl FnOnce(Layout) -> *mut u8,
+    dealloc: impl FnOnce(*mut u8, Layout),
+) -> *mut u8 {
+    let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+    let new_ptr = alloc(new_layout);
+    if !new_ptr.is_null() {
+        unsafe { core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size)) };
+        dealloc(ptr, layout);
+    }
+    new_ptr
+}
+
+unsafe impl GlobalAlloc for O1Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        raw_global_alloc(layout, |size| self.allocate(size), self.on_oom.get())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { raw_global_dealloc(ptr, layout, |p| self.free(p)) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            raw_global_realloc(
+                ptr,
+                layout,
+                new_size,
+                |l| self.alloc(l),
+                |p, l| self.dealloc(p, l),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `over_align`/`over_align_base` are pure pointer arithmetic over a
+    // caller-supplied buffer, so they can be exercised directly without a
+    // live o1heap instance.
+    #[test]
+    fn over_align_round_trips_base_pointer() {
+        let mut buf = [0u8; 8192];
+        for align in [ALIGNMENT * 2, ALIGNMENT * 4, 4096] {
+            let size = 64;
+            assert!(over_aligned_request_size(size, align) <= buf.len());
+            let base = NonNull::new(buf.as_mut_ptr()).unwrap();
+
+            let aligned = unsafe { over_align(base, align) };
+            assert_eq!(aligned.as_ptr() as usize % align, 0);
+
+            let recovered = unsafe { over_align_base(aligned) };
+            assert_eq!(recovered, base);
+        }
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        if let Some(ptr) = NonNull::new(ptr) {
-            unsafe { self.free(ptr) }
+    #[test]
+    fn size_class_is_monotonic_and_bounded_below() {
+        assert_eq!(size_class(0), MIN_FRAGMENT_SIZE);
+        let mut prev = size_class(0);
+        for size in [1, ALIGNMENT, ALIGNMENT * 3, 4096] {
+            let class = size_class(size);
+            assert!(class >= prev);
+            prev = class;
+            assert!(class >= size + FRAGMENT_HEADER_SIZE);
+            assert!(class.is_power_of_two());
+            assert!(class >= MIN_FRAGMENT_SIZE);
         }
     }
 }
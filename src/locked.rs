@@ -0,0 +1,154 @@
+//! Thread-safe [`O1Heap`](crate::O1Heap)-like allocator for multi-core
+//! targets.
+//!
+//! [`O1Heap`](crate::O1Heap) and [`O1HeapGlobalAlloc`](crate::O1HeapGlobalAlloc)
+//! do no locking of their own: they require external synchronization when
+//! shared across cores. `LockedO1Heap` removes that requirement by
+//! locking around every call into o1heap with a [`lock_api::RawMutex`],
+//! mirroring how `talc` and `blog_os` wrap an inner allocator in a spinlock
+//! so it can back a single `#[global_allocator]` shared by all cores.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+
+use lock_api::{Mutex, RawMutex};
+
+use crate::{
+    o1heapAllocate, o1heapFree, o1heapInit, raw_global_alloc, raw_global_dealloc,
+    raw_global_realloc, InitError, O1HeapInstance, ALIGNMENT,
+};
+
+/// State guarded by `LockedO1Heap`'s mutex: the heap instance pointer and
+/// the OOM callback, locked together so `set_on_oom` can't race `init`.
+struct LockedInner {
+    instance: *mut O1HeapInstance,
+    on_oom: Option<fn(Layout) -> *mut u8>,
+}
+
+/// A [`lock_api::RawMutex`]-guarded o1heap instance.
+///
+/// Use [`SpinLockedO1Heap`] for a ready-to-go `no_std` instantiation backed
+/// by a spinlock, or supply your own `R` (e.g. an OS mutex) when one is
+/// available.
+pub struct LockedO1Heap<R: RawMutex> {
+    inner: Mutex<R, LockedInner>,
+}
+
+// SAFETY: every access to the shared instance pointer is taken through
+// `self.inner.lock()`, so concurrent access from multiple cores is
+// synchronized by `R`.
+unsafe impl<R: RawMutex + Send> Sync for LockedO1Heap<R> {}
+
+impl<R: RawMutex> LockedO1Heap<R> {
+    /// Create a new uninitialized heap.
+    ///
+    /// You must call [`init`](Self::init) before any allocations.
+    pub const fn empty() -> Self {
+        Self {
+            inner: Mutex::const_new(
+                R::INIT,
+                LockedInner {
+                    instance: core::ptr::null_mut(),
+                    on_oom: None,
+                },
+            ),
+        }
+    }
+
+    /// Registers a callback invoked when an allocation request cannot be
+    /// satisfied, before [`alloc`](GlobalAlloc::alloc) returns null to the
+    /// caller.
+    ///
+    /// The callback receives the failing [`Layout`] and may return a
+    /// fallback pointer, or null to report the failure as usual. A returned
+    /// pointer is later released through [`dealloc`](GlobalAlloc::dealloc)
+    /// like any other allocation, i.e. handed to o1heap's free routine;
+    /// pointers from outside this heap are not told apart.
+    pub fn set_on_oom(&self, callback: fn(Layout) -> *mut u8) {
+        self.inner.lock().on_oom = Some(callback);
+    }
+
+    /// Initialize the heap with the given memory arena.
+    ///
+    /// # Safety
+    ///
+    /// - Must be called exactly once before any allocations.
+    /// - `start` must be aligned to [`ALIGNMENT`] bytes.
+    /// - `start` must point to at least `size` bytes of valid memory.
+    /// - The memory must remain valid for the lifetime of the heap.
+    pub unsafe fn init(&self, start: *mut u8, size: usize) -> Result<(), InitError> {
+        let mut guard = self.inner.lock();
+        let instance = unsafe { o1heapInit(start.cast(), size) };
+        if instance.is_null() {
+            return Err(InitError);
+        }
+        guard.instance = instance;
+        Ok(())
+    }
+
+    /// Allocate memory of the given size.
+    ///
+    /// Returns a pointer aligned to [`ALIGNMENT`], or `None` if allocation
+    /// fails.
+    pub fn allocate(&self, size: usize) -> Option<NonNull<u8>> {
+        // Keep the guard alive across the FFI call: o1heapAllocate mutates
+        // the heap's internal free-list, so dropping the lock beforehand
+        // (e.g. by reading `instance` into a local first) would let two
+        // cores run it concurrently and race on that free-list.
+        let guard = self.inner.lock();
+        debug_assert!(!guard.instance.is_null(), "LockedO1Heap not initialized");
+        let ptr = unsafe { o1heapAllocate(guard.instance, size) };
+        drop(guard);
+        NonNull::new(ptr.cast())
+    }
+
+    /// Free previously allocated memory.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` must have been returned by [`allocate`](Self::allocate) on
+    ///   this heap.
+    /// - `ptr` must not have been freed already.
+    pub unsafe fn free(&self, ptr: NonNull<u8>) {
+        // See `allocate`: the guard must stay alive across the FFI call.
+        let guard = self.inner.lock();
+        debug_assert!(!guard.instance.is_null(), "LockedO1Heap not initialized");
+        unsafe { o1heapFree(guard.instance, ptr.as_ptr().cast()) }
+        drop(guard);
+    }
+}
+
+unsafe impl<R: RawMutex> GlobalAlloc for LockedO1Heap<R> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let on_oom = self.inner.lock().on_oom;
+        raw_global_alloc(layout, |size| self.allocate(size), on_oom)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { raw_global_dealloc(ptr, layout, |p| self.free(p)) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            raw_global_realloc(
+                ptr,
+                layout,
+                new_size,
+                |l| self.alloc(l),
+                |p, l| self.dealloc(p, l),
+            )
+        }
+    }
+}
+
+/// [`LockedO1Heap`] backed by [`spin::Mutex`](spin::RawMutex)'s raw mutex, for
+/// `no_std` use on multi-core Cortex-A/RISC-V targets without an OS mutex.
+///
+/// ```ignore
+/// use o1heap::SpinLockedO1Heap;
+///
+/// #[global_allocator]
+/// static HEAP: SpinLockedO1Heap = SpinLockedO1Heap::empty();
+/// ```
+#[cfg(feature = "spin")]
+pub type SpinLockedO1Heap = LockedO1Heap<spin::RawMutex>;
@@ -0,0 +1,161 @@
+//! Auto-initializing global allocator backed by a fixed-size static arena.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{
+    o1heapAllocate, o1heapFree, o1heapInit, raw_global_alloc, raw_global_dealloc,
+    raw_global_realloc, O1HeapInstance, OnOomCell,
+};
+
+const UNINIT: u8 = 0;
+const INITIALIZING: u8 = 1;
+const READY: u8 = 2;
+const FAILED: u8 = 3;
+
+/// A [`GlobalAlloc`] owning an internal `N`-byte arena that initializes
+/// itself lazily on first use.
+///
+/// Unlike [`O1Heap`](crate::O1Heap) and
+/// [`O1HeapGlobalAlloc`](crate::O1HeapGlobalAlloc), no explicit `init` call
+/// is required, which makes it safe to use directly as a
+/// `#[global_allocator]` in hosted environments where the runtime may
+/// allocate before `main` runs:
+///
+/// ```ignore
+/// use o1heap::O1HeapStatic;
+///
+/// #[global_allocator]
+/// static HEAP: O1HeapStatic<8192> = O1HeapStatic::new();
+/// ```
+///
+/// First-use initialization is synchronized by a `compare_exchange`-driven
+/// state machine, so it is safe even when multiple threads race to allocate
+/// before the heap has been set up (see [`LockedO1Heap`](crate::LockedO1Heap)
+/// if you additionally need every allocation, not just initialization, to be
+/// synchronized across cores). If `N` is too small for o1heap to initialize
+/// (see [`min_arena_size`](crate::min_arena_size)), the heap latches into a
+/// permanently-failed state instead of retrying: every subsequent allocation
+/// simply fails (returning null, or `on_oom`'s fallback) rather than calling
+/// into o1heap with a null instance.
+#[repr(C, align(32))]
+pub struct O1HeapStatic<const N: usize> {
+    arena: UnsafeCell<[MaybeUninit<u8>; N]>,
+    instance: UnsafeCell<*mut O1HeapInstance>,
+    state: AtomicU8,
+    on_oom: OnOomCell,
+}
+
+unsafe impl<const N: usize> Sync for O1HeapStatic<N> {}
+
+impl<const N: usize> O1HeapStatic<N> {
+    /// Create a new, not-yet-initialized static heap.
+    ///
+    /// The backing arena is initialized lazily, on the first allocation.
+    pub const fn new() -> Self {
+        Self {
+            arena: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            instance: UnsafeCell::new(core::ptr::null_mut()),
+            state: AtomicU8::new(UNINIT),
+            on_oom: OnOomCell::new(),
+        }
+    }
+
+    /// Registers a callback invoked when an allocation request cannot be
+    /// satisfied, before [`alloc`](GlobalAlloc::alloc) returns null to the
+    /// caller.
+    ///
+    /// The callback receives the failing [`Layout`] and may return a
+    /// fallback pointer, or null to report the failure as usual. A returned
+    /// pointer is later released through [`dealloc`](GlobalAlloc::dealloc)
+    /// like any other allocation, i.e. handed to o1heap's free routine;
+    /// pointers from outside this heap are not told apart.
+    pub fn set_on_oom(&self, callback: fn(Layout) -> *mut u8) {
+        self.on_oom.set(callback);
+    }
+
+    /// Returns the heap instance, initializing it first if necessary.
+    ///
+    /// Returns `None` if the arena is too small for o1heap to initialize, or
+    /// if another thread's initialization attempt already failed for that
+    /// reason -- `N` is fixed at compile time, so a failed attempt can never
+    /// succeed on retry.
+    ///
+    /// Initialization races are resolved with a `compare_exchange` on
+    /// `state`: the thread that wins the transition from `UNINIT` to
+    /// `INITIALIZING` runs `o1heapInit` alone, while every other thread
+    /// spins until `state` reaches `READY`/`FAILED` instead of re-running
+    /// `o1heapInit` over the same arena concurrently.
+    fn get_or_init(&self) -> Option<*mut O1HeapInstance> {
+        loop {
+            match self.state.compare_exchange(
+                UNINIT,
+                INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let arena = self.arena.get().cast::<u8>();
+                    let instance = unsafe { o1heapInit(arena.cast(), N) };
+                    if instance.is_null() {
+                        self.state.store(FAILED, Ordering::Release);
+                        return None;
+                    }
+                    unsafe { *self.instance.get() = instance };
+                    self.state.store(READY, Ordering::Release);
+                    return Some(instance);
+                }
+                Err(READY) => return Some(unsafe { *self.instance.get() }),
+                Err(FAILED) => return None,
+                Err(INITIALIZING) => core::hint::spin_loop(),
+                Err(_) => unreachable!(),
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for O1HeapStatic<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<const N: usize> GlobalAlloc for O1HeapStatic<N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.get_or_init() {
+            Some(instance) => raw_global_alloc(
+                layout,
+                |size| NonNull::new(unsafe { o1heapAllocate(instance, size) }.cast()),
+                self.on_oom.get(),
+            ),
+            None => raw_global_alloc(layout, |_| None, self.on_oom.get()),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let Some(instance) = self.get_or_init() else {
+            return;
+        };
+        unsafe {
+            raw_global_dealloc(ptr, layout, |p| {
+                o1heapFree(instance, p.as_ptr() as *mut c_void)
+            })
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        unsafe {
+            raw_global_realloc(
+                ptr,
+                layout,
+                new_size,
+                |l| self.alloc(l),
+                |p, l| self.dealloc(p, l),
+            )
+        }
+    }
+}